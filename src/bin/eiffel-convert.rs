@@ -0,0 +1,42 @@
+//! One-shot offline converter between `Backend` on-disk formats, e.g.:
+//!
+//! ```text
+//! eiffel-convert aof:./data.aof snapshot:./data.snapshot
+//! ```
+
+use eiffel::backend::{convert, AofBackend, Backend, SnapshotBackend};
+
+fn open_backend(spec: &str) -> Box<dyn Backend> {
+    let (kind, path) = spec.split_once(':').unwrap_or_else(|| {
+        eprintln!("expected `<aof|snapshot>:<path>`, got `{spec}`");
+        std::process::exit(2);
+    });
+
+    match kind {
+        "aof" => Box::new(AofBackend::open(path).expect("failed to open AOF backend")),
+        "snapshot" => Box::new(SnapshotBackend::open(path).expect("failed to open snapshot backend")),
+        other => {
+            eprintln!("unknown backend kind `{other}`, expected `aof` or `snapshot`");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [from_spec, to_spec] = args.as_slice() else {
+        eprintln!("usage: eiffel-convert <from> <to>");
+        std::process::exit(2);
+    };
+
+    let from = open_backend(from_spec);
+    let to = open_backend(to_spec);
+
+    match convert(from.as_ref(), to.as_ref()) {
+        Ok(count) => println!("converted {count} entries from {from_spec} to {to_spec}"),
+        Err(e) => {
+            eprintln!("conversion failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
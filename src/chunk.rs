@@ -0,0 +1,89 @@
+//! Content-defined chunking for large `Bson::Binary`/`Bson::Document`
+//! values, so identical substrings across different keys (near-duplicate
+//! logs, slightly-edited documents, ...) are only stored once.
+//!
+//! Boundaries are picked by a gear hash over a sliding 64-byte window:
+//! content determines where a chunk ends, not its offset, so inserting or
+//! removing bytes earlier in the value only disturbs the chunks next to
+//! the edit instead of reshuffling everything after it.
+
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+
+pub const MIN_CHUNK: usize = 2 * 1024;
+pub const MAX_CHUNK: usize = 64 * 1024;
+// 13 zero bits below the mask give a boundary on average every 2^13 = 8 KiB
+// once a chunk has passed MIN_CHUNK, i.e. an average chunk of about 10 KiB.
+const BOUNDARY_MASK: u64 = (1 << 13) - 1;
+const WINDOW: usize = 64;
+
+/// Content address of a chunk: the SHA-256 of its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkId([u8; 32]);
+
+impl ChunkId {
+    pub fn of(bytes: &[u8]) -> ChunkId {
+        let digest = Sha256::digest(bytes);
+        let mut id = [0u8; 32];
+        id.copy_from_slice(&digest);
+        ChunkId(id)
+    }
+}
+
+fn gear_table() -> &'static [u64; 256] {
+    static TABLE: OnceLock<[u64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        // A fixed-seed splitmix64 stream: deterministic across runs (so
+        // chunk boundaries are stable between processes) without pulling
+        // in a `rand` dependency for what's effectively a constant table.
+        let mut seed: u64 = 0x9E3779B97F4A7C15;
+        let mut table = [0u64; 256];
+        for slot in table.iter_mut() {
+            seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            *slot = z ^ (z >> 31);
+        }
+        table
+    })
+}
+
+/// Splits `data` into content-defined chunks, each in `[MIN_CHUNK,
+/// MAX_CHUNK]` bytes (except possibly the final one, which just takes
+/// whatever is left). Returns the byte ranges rather than copies so
+/// callers can decide how to store each piece.
+pub fn chunk_boundaries(data: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let table = gear_table();
+    let mut ranges = Vec::new();
+    let mut start = 0usize;
+    let mut hash: u64 = 0;
+
+    if data.len() <= MIN_CHUNK {
+        if !data.is_empty() {
+            ranges.push(0..data.len());
+        }
+        return ranges;
+    }
+
+    let mut i = 0usize;
+    while i < data.len() {
+        hash = (hash << 1).wrapping_add(table[data[i] as usize]);
+        let len = i + 1 - start;
+
+        let at_boundary = len >= WINDOW && hash & BOUNDARY_MASK == 0;
+        if (len >= MIN_CHUNK && at_boundary) || len >= MAX_CHUNK {
+            ranges.push(start..i + 1);
+            start = i + 1;
+            hash = 0;
+        }
+        i += 1;
+    }
+
+    if start < data.len() {
+        ranges.push(start..data.len());
+    }
+
+    ranges
+}
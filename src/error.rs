@@ -0,0 +1,40 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum EiffelError {
+    WrongType,
+    Io(std::io::Error),
+    Corrupt(String),
+    GuardFailed,
+}
+
+impl fmt::Display for EiffelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EiffelError::WrongType => write!(f, "value is not the expected type"),
+            EiffelError::Io(e) => write!(f, "io error: {e}"),
+            EiffelError::Corrupt(msg) => write!(f, "corrupt data: {msg}"),
+            EiffelError::GuardFailed => write!(f, "batch precondition was not met"),
+        }
+    }
+}
+
+impl std::error::Error for EiffelError {}
+
+impl From<std::io::Error> for EiffelError {
+    fn from(e: std::io::Error) -> Self {
+        EiffelError::Io(e)
+    }
+}
+
+impl From<bson::ser::Error> for EiffelError {
+    fn from(e: bson::ser::Error) -> Self {
+        EiffelError::Corrupt(e.to_string())
+    }
+}
+
+impl From<bson::de::Error> for EiffelError {
+    fn from(e: bson::de::Error) -> Self {
+        EiffelError::Corrupt(e.to_string())
+    }
+}
@@ -0,0 +1,66 @@
+//! Hybrid Logical Clock: a `(physical, counter)` pair that gives entries a
+//! total order across independent writers without coordination, so
+//! replicas can converge on the same state via last-writer-wins merges.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A single HLC timestamp. Ordering is lexicographic on
+/// `(physical, counter)`, which is what makes last-writer-wins merges
+/// deterministic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct HlcTimestamp {
+    pub physical: u64,
+    pub counter: u32,
+}
+
+/// The mutable clock state a node advances on every local mutation and
+/// folds remote timestamps into on every merge.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HlcClock {
+    last_physical: u64,
+    counter: u32,
+}
+
+fn wall_clock_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+impl HlcClock {
+    pub fn new() -> HlcClock {
+        HlcClock { last_physical: 0, counter: 0 }
+    }
+
+    /// Advances the clock for a local mutation and returns the timestamp
+    /// to stamp it with.
+    pub fn tick(&mut self) -> HlcTimestamp {
+        let now_ms = wall_clock_ms();
+        if now_ms > self.last_physical {
+            self.last_physical = now_ms;
+            self.counter = 0;
+        } else {
+            self.counter += 1;
+        }
+        HlcTimestamp { physical: self.last_physical, counter: self.counter }
+    }
+
+    /// Folds a remote timestamp into the clock, per the standard HLC
+    /// receive rule, and returns the timestamp the resulting local event
+    /// (e.g. a merge) should be stamped with.
+    pub fn update(&mut self, remote: HlcTimestamp) -> HlcTimestamp {
+        let now_ms = wall_clock_ms();
+        let new_physical = self.last_physical.max(remote.physical).max(now_ms);
+
+        self.counter = if new_physical == self.last_physical && new_physical == remote.physical {
+            self.counter.max(remote.counter) + 1
+        } else if new_physical == self.last_physical {
+            self.counter + 1
+        } else if new_physical == remote.physical {
+            remote.counter + 1
+        } else {
+            0
+        };
+
+        self.last_physical = new_physical;
+        HlcTimestamp { physical: self.last_physical, counter: self.counter }
+    }
+}
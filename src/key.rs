@@ -1,5 +1,5 @@
 
-use bson::{oid::ObjectId, Uuid};
+use bson::{oid::ObjectId, Bson, Uuid};
 
 
 
@@ -68,3 +68,33 @@ impl ToKey for Uuid {
         Key::Uuid(self.to_owned())
     }
 }
+
+
+impl Key {
+    /// Encodes the key as a tagged `Bson` value so it can be written to a
+    /// persistence backend and recovered losslessly on rehydration.
+    pub fn to_bson(&self) -> Bson {
+        match self {
+            Key::Uint32(v) => Bson::Document(bson::doc! { "t": "u32", "v": *v as i64 }),
+            Key::Uint64(v) => Bson::Document(bson::doc! { "t": "u64", "v": *v as i64 }),
+            Key::String(v) => Bson::Document(bson::doc! { "t": "str", "v": v.clone() }),
+            Key::Uuid(v) => Bson::Document(bson::doc! { "t": "uuid", "v": v.to_owned() }),
+            Key::ObjectId(v) => Bson::Document(bson::doc! { "t": "oid", "v": v.to_owned() }),
+        }
+    }
+
+    /// The inverse of [`Key::to_bson`]. Returns `None` if the document does
+    /// not carry a recognized `t`/`v` pair.
+    pub fn from_bson(bson: &Bson) -> Option<Key> {
+        let doc = bson.as_document()?;
+        let tag = doc.get_str("t").ok()?;
+        match tag {
+            "u32" => Some(Key::Uint32(doc.get_i64("v").ok()? as u32)),
+            "u64" => Some(Key::Uint64(doc.get_i64("v").ok()? as u64)),
+            "str" => Some(Key::String(doc.get_str("v").ok()?.to_owned())),
+            "uuid" => doc.get("v").and_then(Bson::as_uuid).map(Key::Uuid),
+            "oid" => doc.get_object_id("v").ok().map(Key::ObjectId),
+            _ => None,
+        }
+    }
+}
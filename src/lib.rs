@@ -0,0 +1,8 @@
+pub mod backend;
+pub mod chunk;
+pub mod error;
+pub mod hlc;
+pub mod key;
+pub mod query;
+pub mod storage;
+pub mod watch;
@@ -0,0 +1,327 @@
+//! A small scan-and-filter query language over stored BSON fields, for
+//! callers that want to find entries by their contents instead of only by
+//! exact `Key`. [`parse_query`] turns a text form like
+//! `WHERE status = "active" AND age >= 18 LIMIT 50` into a [`Query`];
+//! `DataStore::query` can just as well be handed a [`Query`] built by hand.
+//!
+//! A leading `KEY PREFIX "..."` restricts the scan to keys sharing that
+//! prefix (only `Key::String` supports a meaningful prefix — other key
+//! kinds fall back to exact match, mirroring `watch`'s pattern matching),
+//! e.g. `KEY PREFIX "user:" WHERE age >= 18`. At least one of `KEY PREFIX`
+//! or `WHERE` must be present.
+
+use bson::Bson;
+
+use crate::error::EiffelError;
+use crate::key::Key;
+
+/// A dotted path into a (possibly nested) `Bson::Document`, e.g.
+/// `user.age` for `{ user: { age: 30 } }`. An empty path refers to the
+/// value itself, which lets a predicate match scalar entries too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FieldPath(Vec<String>);
+
+impl FieldPath {
+    pub fn new(parts: Vec<String>) -> FieldPath {
+        FieldPath(parts)
+    }
+
+    pub fn parse(dotted: &str) -> FieldPath {
+        FieldPath(dotted.split('.').map(str::to_owned).collect())
+    }
+
+    pub fn get<'a>(&self, value: &'a Bson) -> Option<&'a Bson> {
+        let mut current = value;
+        for part in &self.0 {
+            current = current.as_document()?.get(part)?;
+        }
+        Some(current)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn matches(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            CmpOp::Eq => ordering == Equal,
+            CmpOp::Ne => ordering != Equal,
+            CmpOp::Lt => ordering == Less,
+            CmpOp::Le => ordering != Greater,
+            CmpOp::Gt => ordering == Greater,
+            CmpOp::Ge => ordering != Less,
+        }
+    }
+}
+
+/// A predicate over an entry's value. `And` is the only combinator for
+/// now, matching the text form's `AND`-only grammar.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Compare { field: FieldPath, op: CmpOp, value: Bson },
+    And(Vec<Filter>),
+}
+
+impl Filter {
+    pub fn matches(&self, value: &Bson) -> bool {
+        match self {
+            Filter::Compare { field, op, value: rhs } => match field.get(value) {
+                Some(lhs) => compare_bson(lhs, rhs).map(|ord| op.matches(ord)).unwrap_or(false),
+                None => false,
+            },
+            Filter::And(parts) => parts.iter().all(|f| f.matches(value)),
+        }
+    }
+}
+
+/// Orders `a` against `b` if they're numerically or lexicographically
+/// comparable; mismatched or incomparable kinds (e.g. a string against a
+/// document) are simply never `true` for any `CmpOp`.
+fn compare_bson(a: &Bson, b: &Bson) -> Option<std::cmp::Ordering> {
+    if let (Some(a), Some(b)) = (as_f64(a), as_f64(b)) {
+        return a.partial_cmp(&b);
+    }
+    match (a, b) {
+        (Bson::String(a), Bson::String(b)) => Some(a.cmp(b)),
+        (Bson::Boolean(a), Bson::Boolean(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_f64(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(v) => Some(*v as f64),
+        Bson::Int64(v) => Some(*v as f64),
+        Bson::Double(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// A parsed query: an optional `Key` prefix to restrict the scan to, an
+/// optional filter over entry contents, an optional result cap, and a
+/// cursor (number of matches to skip before collecting), mirroring the
+/// position-based pagination `DataStore::cursor` already offers over the
+/// unfiltered keyspace.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub key_prefix: Option<Key>,
+    pub filter: Option<Filter>,
+    pub limit: Option<usize>,
+    pub cursor: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Key,
+    Prefix,
+    Where,
+    And,
+    Limit,
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Op(CmpOp),
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, EiffelError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c == '"' {
+            let mut s = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                s.push(chars[i]);
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(EiffelError::Corrupt("unterminated string literal".into()));
+            }
+            i += 1;
+            tokens.push(Token::Str(s));
+            continue;
+        }
+
+        if c == '=' {
+            tokens.push(Token::Op(CmpOp::Eq));
+            i += 1;
+            continue;
+        }
+        if c == '!' && chars.get(i + 1) == Some(&'=') {
+            tokens.push(Token::Op(CmpOp::Ne));
+            i += 2;
+            continue;
+        }
+        if c == '<' {
+            if chars.get(i + 1) == Some(&'=') {
+                tokens.push(Token::Op(CmpOp::Le));
+                i += 2;
+            } else {
+                tokens.push(Token::Op(CmpOp::Lt));
+                i += 1;
+            }
+            continue;
+        }
+        if c == '>' {
+            if chars.get(i + 1) == Some(&'=') {
+                tokens.push(Token::Op(CmpOp::Ge));
+                i += 2;
+            } else {
+                tokens.push(Token::Op(CmpOp::Gt));
+                i += 1;
+            }
+            continue;
+        }
+
+        if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| EiffelError::Corrupt(format!("invalid integer literal `{text}`")))?;
+            tokens.push(Token::Int(n));
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(match word.to_ascii_uppercase().as_str() {
+                "KEY" => Token::Key,
+                "PREFIX" => Token::Prefix,
+                "WHERE" => Token::Where,
+                "AND" => Token::And,
+                "LIMIT" => Token::Limit,
+                _ => Token::Ident(word),
+            });
+            continue;
+        }
+
+        return Err(EiffelError::Corrupt(format!("unexpected character `{c}`")));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let tok = self.tokens.get(self.pos);
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn parse_condition(&mut self) -> Result<Filter, EiffelError> {
+        let field = match self.next() {
+            Some(Token::Ident(name)) => FieldPath::parse(name),
+            other => return Err(EiffelError::Corrupt(format!("expected a field name, got {other:?}"))),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => *op,
+            other => return Err(EiffelError::Corrupt(format!("expected a comparison operator, got {other:?}"))),
+        };
+        let value = match self.next() {
+            Some(Token::Str(s)) => Bson::String(s.clone()),
+            Some(Token::Int(n)) => Bson::Int64(*n),
+            other => return Err(EiffelError::Corrupt(format!("expected a literal value, got {other:?}"))),
+        };
+        Ok(Filter::Compare { field, op, value })
+    }
+
+    fn parse_key_literal(&mut self) -> Result<Key, EiffelError> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(Key::String(s.clone())),
+            Some(Token::Int(n)) if *n >= 0 => Ok(Key::Uint64(*n as u64)),
+            other => Err(EiffelError::Corrupt(format!("expected a key literal after KEY PREFIX, got {other:?}"))),
+        }
+    }
+
+    fn parse_query(&mut self) -> Result<Query, EiffelError> {
+        let mut query = Query::default();
+
+        if matches!(self.peek(), Some(Token::Key)) {
+            self.pos += 1;
+            match self.next() {
+                Some(Token::Prefix) => {}
+                other => return Err(EiffelError::Corrupt(format!("expected PREFIX after KEY, got {other:?}"))),
+            }
+            query.key_prefix = Some(self.parse_key_literal()?);
+        }
+
+        if matches!(self.peek(), Some(Token::Where)) {
+            self.pos += 1;
+
+            let mut conditions = vec![self.parse_condition()?];
+            while matches!(self.peek(), Some(Token::And)) {
+                self.pos += 1;
+                conditions.push(self.parse_condition()?);
+            }
+
+            query.filter = Some(if conditions.len() == 1 {
+                conditions.remove(0)
+            } else {
+                Filter::And(conditions)
+            });
+        } else if query.key_prefix.is_none() {
+            return Err(EiffelError::Corrupt(format!("expected KEY PREFIX or WHERE, got {:?}", self.peek())));
+        }
+
+        if matches!(self.peek(), Some(Token::Limit)) {
+            self.pos += 1;
+            match self.next() {
+                Some(Token::Int(n)) if *n >= 0 => query.limit = Some(*n as usize),
+                other => return Err(EiffelError::Corrupt(format!("expected an integer after LIMIT, got {other:?}"))),
+            }
+        }
+
+        if self.pos != self.tokens.len() {
+            return Err(EiffelError::Corrupt("unexpected trailing tokens after query".into()));
+        }
+
+        Ok(query)
+    }
+}
+
+/// Parses a text query such as `WHERE status = "active" AND age >= 18
+/// LIMIT 50`, or `KEY PREFIX "user:" WHERE age >= 18`, into a [`Query`]
+/// ready for `DataStore::query`.
+pub fn parse_query(text: &str) -> Result<Query, EiffelError> {
+    let tokens = lex(text)?;
+    Parser { tokens: &tokens, pos: 0 }.parse_query()
+}
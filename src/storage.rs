@@ -1,13 +1,29 @@
 use bson::{Document, Bson};
 use indexmap::IndexMap;
 use parking_lot::RwLock;
-use tokio::sync::Notify;
+use tokio::sync::{mpsc, Notify};
 use tokio::time::{self, Duration, Instant};
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::backend::{Backend, PersistedEntry};
+use crate::chunk::{self, ChunkId};
 use crate::error::EiffelError;
+use crate::hlc::{HlcClock, HlcTimestamp};
 use crate::key::Key;
+use crate::query::{self, CmpOp, FieldPath, Filter, Query};
+use crate::watch::{self, PatternWatchStream, WatchEvent, WatchStream};
+
+/// Values at or above this size, of a chunkable `Bson` kind, are
+/// content-chunked and deduplicated when written, instead of stored
+/// inline. Values rehydrated by `DataStore::with_backend` stay inline.
+const CHUNK_THRESHOLD: usize = 4 * 1024;
+
+/// How often `with_backend` drives `Backend::flush`, e.g. so a
+/// `SnapshotBackend` rewrites its file on disk instead of only ever
+/// buffering mutations in its in-memory mirror.
+const BACKEND_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
 
 
 #[derive(Debug)]
@@ -20,33 +36,320 @@ pub struct DataStore {
     shared: Arc<Shared>,
 }
 
-#[derive(Debug)]
 struct Shared {
     state: RwLock<State>,
     background_task: Notify,
+    backend: Option<Arc<dyn Backend>>,
+}
+
+impl std::fmt::Debug for Shared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("state", &self.state)
+            .field("has_backend", &self.backend.is_some())
+            .finish()
+    }
 }
 
-#[derive(Debug)]
 struct State {
 
     entries: IndexMap<Key, Entry>,
-   
+
     expirations: BTreeMap<(Instant, u64), Key>,
 
-    // // maybe change u8 to channel oneshot
-    // watched_keys: IndexMap<Key, u8>,
-   
+    // Subscribers registered via `DataStore::watch`, fanned out to on
+    // every mutation of their key.
+    watched_keys: IndexMap<Key, Vec<mpsc::Sender<WatchEvent>>>,
+
+    // Subscribers registered via `DataStore::subscribe_pattern`, matched
+    // against the mutated key's prefix.
+    pattern_watchers: Vec<(Key, mpsc::Sender<(Key, WatchEvent)>)>,
+
+    // Per-key `Notify` handles backing `get_await`, created lazily the
+    // first time someone blocks on a key.
+    key_notifies: HashMap<Key, Arc<Notify>>,
+
+    // Hybrid logical clock driving the timestamp on every local mutation
+    // and folded into on every remote `merge`.
+    clock: HlcClock,
+
+    // Content-addressed chunk store backing `EntryData::Chunked` values,
+    // reference-counted so a chunk shared by several keys (or several
+    // versions of one key) is only freed once nothing points at it.
+    chunks: HashMap<ChunkId, (Arc<[u8]>, u64)>,
+
+    // Secondary indexes created via `DataStore::create_index`, kept in
+    // sync with `entries` on every mutation so `query` can skip the full
+    // scan for equality predicates on an indexed field. Keyed by an
+    // encoded `Bson` (see `index_key`) rather than `Bson` itself, since
+    // `Bson` implements `PartialEq` but not `Ord`.
+    indexes: HashMap<FieldPath, BTreeMap<Vec<u8>, HashSet<Key>>>,
+
     next_id: u64,
-   
+
     shutdown: bool,
 }
 
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("entries", &self.entries)
+            .field("expirations", &self.expirations)
+            .field("next_id", &self.next_id)
+            .field("shutdown", &self.shutdown)
+            .finish()
+    }
+}
+
+impl State {
+    /// Wraps `value` as an `Entry`'s data representation, content-chunking
+    /// it (and interning the chunks, incrementing refcounts on any that
+    /// already existed) if it's a `Binary`/`Document` at or above
+    /// `CHUNK_THRESHOLD`.
+    fn intern_value(&mut self, value: Bson) -> EntryData {
+        let Some((kind, bytes)) = chunkable_bytes(&value) else {
+            return EntryData::Inline(Arc::new(value));
+        };
+
+        let ids = chunk::chunk_boundaries(&bytes)
+            .into_iter()
+            .map(|range| {
+                let piece = &bytes[range];
+                let id = ChunkId::of(piece);
+                let slot = self.chunks.entry(id).or_insert_with(|| (Arc::from(piece), 0));
+                slot.1 += 1;
+                id
+            })
+            .collect();
+
+        EntryData::Chunked { kind, ids }
+    }
+
+    /// Decrements the refcount of every chunk `data` points at, freeing
+    /// any that drop to zero. Called whenever an entry is deleted or
+    /// overwritten.
+    fn release_data(&mut self, data: &EntryData) {
+        if let EntryData::Chunked { ids, .. } = data {
+            for id in ids {
+                if let std::collections::hash_map::Entry::Occupied(mut slot) = self.chunks.entry(*id) {
+                    slot.get_mut().1 -= 1;
+                    if slot.get().1 == 0 {
+                        slot.remove();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reassembles an entry's data back into a `Bson` value, joining its
+    /// chunks in order if it's `Chunked`.
+    fn entry_value(&self, entry: &Entry) -> Arc<Bson> {
+        match &entry.data {
+            EntryData::Inline(value) => value.clone(),
+            EntryData::Chunked { kind, ids } => {
+                let mut bytes = Vec::new();
+                for id in ids {
+                    if let Some((chunk, _)) = self.chunks.get(id) {
+                        bytes.extend_from_slice(chunk);
+                    }
+                }
+                Arc::new(kind.reassemble(bytes))
+            }
+        }
+    }
+
+    /// Adds `key` under every secondary index whose field is present in
+    /// `value`. Called after an entry is inserted.
+    fn index_insert(&mut self, key: &Key, value: &Bson) {
+        for (field, index) in self.indexes.iter_mut() {
+            if let Some(indexed) = field.get(value) {
+                index.entry(index_key(indexed)).or_default().insert(key.clone());
+            }
+        }
+    }
+
+    /// Removes `key` from every secondary index it was filed under for
+    /// `value`, dropping the index's entry for that value once it's empty.
+    /// Called before an entry's old value is discarded (overwrite or
+    /// delete).
+    fn index_remove(&mut self, key: &Key, value: &Bson) {
+        for (field, index) in self.indexes.iter_mut() {
+            if let Some(indexed) = field.get(value) {
+                let encoded = index_key(indexed);
+                if let Some(keys) = index.get_mut(&encoded) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        index.remove(&encoded);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `Bson` implements `PartialEq` but not `Ord` (it holds `f64`s,
+/// documents, ...), so it can't key a `BTreeMap` directly. Encoding it as
+/// its canonical BSON bytes gives a total order that's at least
+/// consistent with equality, which is all an index lookup needs today.
+///
+/// Numeric variants are normalized to `Bson::Double` first so that
+/// `Int32(30)`, `Int64(30)`, and `Double(30.0)` all encode identically —
+/// matching `query::compare_bson`'s numeric equivalence on the scan path.
+/// Without this, creating an index could change a query's results simply
+/// by narrowing an equality lookup to one exact BSON variant.
+fn index_key(value: &Bson) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let canonical = match query::as_f64(value) {
+        Some(n) => Bson::Double(n),
+        None => value.clone(),
+    };
+    bson::doc! { "v": canonical }
+        .to_writer(&mut bytes)
+        .expect("encoding a Bson value for an index key cannot fail");
+    bytes
+}
+
+/// Returns the set of keys a `query` can restrict its scan to, if its
+/// filter contains an equality predicate on an indexed field. `None`
+/// means "no usable index — scan every entry".
+///
+/// Candidates come out of a `HashSet`, so they're sorted back into
+/// `entries`' own insertion order before being returned — otherwise
+/// `cursor`/`limit` pagination would be stable on a full scan but
+/// shuffle from call to call once an index was used for the same query.
+fn indexed_candidates(state: &State, filter: Option<&Filter>) -> Option<Vec<Key>> {
+    let (field, value) = find_indexed_equality(filter?)?;
+    let index = state.indexes.get(field)?;
+    let encoded = index_key(value);
+    let mut keys: Vec<Key> = index.get(&encoded).map(|keys| keys.iter().cloned().collect()).unwrap_or_default();
+    keys.sort_by_key(|key| state.entries.get_index_of(key));
+    Some(keys)
+}
+
+fn find_indexed_equality(filter: &Filter) -> Option<(&FieldPath, &Bson)> {
+    match filter {
+        Filter::Compare { field, op: CmpOp::Eq, value } => Some((field, value)),
+        Filter::Compare { .. } => None,
+        Filter::And(parts) => parts.iter().find_map(find_indexed_equality),
+    }
+}
 
 #[derive(Debug)]
 struct Entry {
     id: u64,
-    data: Arc<Bson>,
+    data: EntryData,
     expires_at: Option<Instant>,
+    hlc: HlcTimestamp,
+}
+
+/// How an `Entry`'s value is represented in memory: either inline, or as
+/// an ordered list of content-addressed chunks that must be reassembled
+/// (via [`State::entry_value`]) to get the original `Bson` back.
+#[derive(Debug)]
+enum EntryData {
+    Inline(Arc<Bson>),
+    Chunked { kind: ChunkedKind, ids: Vec<ChunkId> },
+}
+
+impl EntryData {
+    /// `as_i64` only ever applies to the small inline counters `incr`/
+    /// `decr` deal in; a chunked value is never one of those, so it's
+    /// just `None`.
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            EntryData::Inline(value) => value.as_i64(),
+            EntryData::Chunked { .. } => None,
+        }
+    }
+}
+
+/// What a chunked value's bytes deserialize back into.
+#[derive(Debug, Clone, Copy)]
+enum ChunkedKind {
+    Binary(bson::spec::BinarySubtype),
+    Document,
+}
+
+impl ChunkedKind {
+    fn reassemble(self, bytes: Vec<u8>) -> Bson {
+        match self {
+            ChunkedKind::Binary(subtype) => Bson::Binary(bson::Binary { subtype, bytes }),
+            ChunkedKind::Document => {
+                Document::from_reader(&mut bytes.as_slice())
+                    .map(Bson::Document)
+                    .unwrap_or(Bson::Null)
+            }
+        }
+    }
+}
+
+/// Returns the raw bytes to chunk for `value`, and what they'd
+/// deserialize back into, if `value` is large enough to be worth
+/// chunking. Anything else (small values, or types chunking doesn't
+/// apply to) is left inline.
+fn chunkable_bytes(value: &Bson) -> Option<(ChunkedKind, Vec<u8>)> {
+    match value {
+        Bson::Binary(bin) if bin.bytes.len() >= CHUNK_THRESHOLD => {
+            Some((ChunkedKind::Binary(bin.subtype), bin.bytes.clone()))
+        }
+        Bson::Document(doc) => {
+            let mut bytes = Vec::new();
+            doc.to_writer(&mut bytes).ok()?;
+            if bytes.len() >= CHUNK_THRESHOLD {
+                Some((ChunkedKind::Document, bytes))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Entry {
+    fn to_persisted(&self, value: &Bson) -> PersistedEntry {
+        build_persisted(self.id, value, self.expires_at, self.hlc)
+    }
+}
+
+/// Builds the `PersistedEntry` a backend should see for a write, shared by
+/// every mutating path (`Entry::to_persisted` for single-op mutations,
+/// `Batch::commit` for a transaction's `PendingEntry`s) so a new field
+/// only ever needs to be filled in here.
+fn build_persisted(id: u64, value: &Bson, expires_at: Option<Instant>, hlc: HlcTimestamp) -> PersistedEntry {
+    PersistedEntry {
+        id,
+        data: value.clone(),
+        expires_at_ms: expires_at.map(instant_to_epoch_ms),
+        hlc,
+    }
+}
+
+/// Converts a `tokio::time::Instant` deadline into milliseconds since
+/// `UNIX_EPOCH`, so a backend can persist it across a process restart
+/// (an `Instant` is only meaningful within the process that created it).
+fn instant_to_epoch_ms(instant: Instant) -> u64 {
+    let now_instant = Instant::now();
+    let now_epoch_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let offset = instant.saturating_duration_since(now_instant);
+    now_epoch_ms + offset.as_millis() as u64
+}
+
+/// The inverse of [`instant_to_epoch_ms`]. If `epoch_ms` is already in the
+/// past, returns `Instant::now()` so the entry is picked up by the next
+/// purge pass instead of panicking on an underflowed duration.
+fn epoch_ms_to_instant(epoch_ms: u64) -> Instant {
+    let now_epoch_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    if epoch_ms <= now_epoch_ms {
+        Instant::now()
+    } else {
+        Instant::now() + Duration::from_millis(epoch_ms - now_epoch_ms)
+    }
 }
 
 impl DbDropGuard {
@@ -73,53 +376,185 @@ impl DataStore {
             state: RwLock::new(State {
                 entries: IndexMap::new(),
                 expirations: BTreeMap::new(),
+                watched_keys: IndexMap::new(),
+                pattern_watchers: Vec::new(),
+                key_notifies: HashMap::new(),
+                clock: HlcClock::new(),
+                chunks: HashMap::new(),
+                indexes: HashMap::new(),
                 next_id: 0,
                 shutdown: false,
             }),
             background_task: Notify::new(),
+            backend: None,
         });
 
         tokio::spawn(purge_expired_tasks(shared.clone()));
 
         DataStore { shared }
     }
-  
+
+    /// Like [`DataStore::new`], but rehydrates `entries`/`expirations` from
+    /// `backend` before serving any callers, and durably persists every
+    /// subsequent mutation through it. Rehydration runs to completion
+    /// before the purge task is spawned, so the two never race over the
+    /// same `entries` map.
+    pub fn with_backend(backend: Arc<dyn Backend>) -> Result<DataStore, EiffelError> {
+        let loaded = backend.load_all()?;
+
+        let mut entries = IndexMap::new();
+        let mut expirations = BTreeMap::new();
+        let mut next_id = 0u64;
+        let mut clock = HlcClock::new();
+
+        for item in loaded {
+            next_id = next_id.max(item.id + 1);
+            // Folds every rehydrated timestamp into the clock via the HLC
+            // receive rule, so the first local mutation after a restart is
+            // still guaranteed to be newer than anything that was here
+            // before it, the same guarantee `merge` gives a live replica.
+            clock.update(item.hlc);
+            let expires_at = item.expires_at_ms.map(epoch_ms_to_instant);
+            if let Some(when) = expires_at {
+                expirations.insert((when, item.id), item.key.clone());
+            }
+            entries.insert(
+                item.key,
+                Entry {
+                    id: item.id,
+                    data: EntryData::Inline(Arc::new(item.data)),
+                    expires_at,
+                    hlc: item.hlc,
+                },
+            );
+        }
+
+        let shared = Arc::new(Shared {
+            state: RwLock::new(State {
+                entries,
+                expirations,
+                watched_keys: IndexMap::new(),
+                pattern_watchers: Vec::new(),
+                key_notifies: HashMap::new(),
+                clock,
+                chunks: HashMap::new(),
+                indexes: HashMap::new(),
+                next_id,
+                shutdown: false,
+            }),
+            background_task: Notify::new(),
+            backend: Some(backend),
+        });
+
+        tokio::spawn(purge_expired_tasks(shared.clone()));
+        tokio::spawn(flush_backend_task(shared.clone()));
+
+        Ok(DataStore { shared })
+    }
+
     // Sets the value at the specified key.
-    pub fn set(&self, key: Key, value: Bson) {
+    //
+    // If a backend is configured the mutation is handed to it before this
+    // call returns; with a backend that is durable per-call (`AofBackend`),
+    // a caller never observes an acknowledged write that did not make it
+    // to durable storage.
+    pub fn set(&self, key: Key, value: Bson) -> Result<(), EiffelError> {
         let mut state = self.shared.state.write();
 
         let id = state.next_id;
         state.next_id += 1;
 
+        let hlc = state.clock.tick();
+        let data = state.intern_value(value);
+        let entry = Entry { id, data, expires_at: None, hlc };
+        let materialized = state.entry_value(&entry);
+
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&materialized))?;
+        }
 
-        let prev = state.entries.insert(
-            key,
-            Entry {
-                id,
-                data: Arc::new(value),
-                expires_at: None,
-            },
-        );
+        let prev = state.entries.insert(key.clone(), entry);
 
         if let Some(prev) = prev {
             if let Some(when) = prev.expires_at {
                 state.expirations.remove(&(when, prev.id));
             }
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+            state.release_data(&prev.data);
         }
+
+        state.index_insert(&key, &materialized);
+        state.fan_out(&key, WatchEvent::Set(materialized));
+
+        Ok(())
     }
-    
+
     // Gets the value of a key.
     pub fn get(&self, key: &Key) -> Option<Arc<Bson>> {
-    
+
+        let state = self.shared.state.read();
+        state.entries.get(key).map(|entry| state.entry_value(entry))
+    }
+
+    /// Gets the value of a key along with the HLC timestamp it was last
+    /// written at, for callers doing their own replication bookkeeping.
+    pub fn get_with_version(&self, key: &Key) -> Option<(Arc<Bson>, HlcTimestamp)> {
         let state = self.shared.state.read();
-        state.entries.get(key).map(|entry| entry.data.clone())
+        state.entries.get(key).map(|entry| (state.entry_value(entry), entry.hlc))
+    }
+
+    /// Applies a remote write under last-writer-wins semantics: `value` is
+    /// only installed if `remote_ts` is strictly newer than the key's
+    /// current timestamp (or the key doesn't exist yet). Either way the
+    /// local clock is folded forward per the HLC receive rule, so a
+    /// rejected write still advances causality.
+    pub fn merge(&self, key: Key, value: Bson, remote_ts: HlcTimestamp) -> Result<(), EiffelError> {
+        let mut state = self.shared.state.write();
+        state.clock.update(remote_ts);
+
+        if let Some(existing) = state.entries.get(&key) {
+            if existing.hlc >= remote_ts {
+                return Ok(());
+            }
+        }
+
+        let id = match state.entries.get(&key) {
+            Some(entry) => entry.id,
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                id
+            }
+        };
+
+        let data = state.intern_value(value);
+        let entry = Entry { id, data, expires_at: None, hlc: remote_ts };
+        let materialized = state.entry_value(&entry);
+
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&materialized))?;
+        }
+
+        if let Some(prev) = state.entries.insert(key.clone(), entry) {
+            if let Some(when) = prev.expires_at {
+                state.expirations.remove(&(when, prev.id));
+            }
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+            state.release_data(&prev.data);
+        }
+        state.index_insert(&key, &materialized);
+        state.fan_out(&key, WatchEvent::Set(materialized));
+
+        Ok(())
     }
 
     // Sets the Bson value of a key and return its old value.
-    pub fn getset(&self, key: Key, value: Bson) -> Option<Arc<Bson>> {
+    pub fn getset(&self, key: Key, value: Bson) -> Result<Option<Arc<Bson>>, EiffelError> {
         let mut state = self.shared.state.write();
 
-        
+
         let (id, expires_at) = match state.entries.get(&key) {
             Some(entry) => {
                 (entry.id, entry.expires_at)
@@ -131,30 +566,41 @@ impl DataStore {
                 (id, None)
             }
         };
-        
-
-        let prev = state.entries.insert(
-            key,
-            Entry {
-                id,
-                data: Arc::new(value),
-                expires_at,
-            },
-        ).map(|e| e.data);
-        
-        drop(state);
-        return prev
 
+
+        let hlc = state.clock.tick();
+        let data = state.intern_value(value);
+        let entry = Entry { id, data, expires_at, hlc };
+        let materialized = state.entry_value(&entry);
+
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&materialized))?;
+        }
+
+        let prev = state.entries.insert(key.clone(), entry);
+
+        let prev_value = prev.as_ref().map(|entry| state.entry_value(entry));
+        if let Some(prev) = prev {
+            if let Some(prev_value) = &prev_value {
+                state.index_remove(&key, prev_value);
+            }
+            state.release_data(&prev.data);
+        }
+        state.index_insert(&key, &materialized);
+        state.fan_out(&key, WatchEvent::Set(materialized));
+
+        drop(state);
+        Ok(prev_value)
     }
-        
+
     // Gets the values of all the given keys
     pub fn mget(&self, keys: &[Key]) -> Vec<Arc<Bson>> {
         let mut result = Vec::with_capacity(keys.len());
         let state = self.shared.state.read();
-        
+
         for key in keys {
             if let Some(entry) = state.entries.get(key) {
-                result.push(entry.data.clone());
+                result.push(state.entry_value(entry));
             }
         }
 
@@ -162,57 +608,67 @@ impl DataStore {
     }
 
     // Sets the value with the expiry of a key
-    pub fn setex(&self, key: Key, value: Bson, expire: Option<Duration>) {
+    pub fn setex(&self, key: Key, value: Bson, expire: Option<Duration>) -> Result<(), EiffelError> {
         let mut state = self.shared.state.write();
 
-        
+
         let id = state.next_id;
         state.next_id += 1;
 
-    
+
         let mut notify = false;
         let expires_at = expire.map(|duration| {
-            
+
             let when = Instant::now() + duration;
             notify = state
                 .next_expiration()
                 .map(|expiration| expiration > when)
                 .unwrap_or(true);
 
-        
+
             state.expirations.insert((when, id), key.clone());
             when
         });
 
-        let prev = state.entries.insert(
-            key,
-            Entry {
-                id,
-                data: Arc::new(value),
-                expires_at,
-            },
-        );
+        let hlc = state.clock.tick();
+        let data = state.intern_value(value);
+        let entry = Entry { id, data, expires_at, hlc };
+        let materialized = state.entry_value(&entry);
+
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&materialized))?;
+        }
+
+        let prev = state.entries.insert(key.clone(), entry);
 
         if let Some(prev) = prev {
             if let Some(when) = prev.expires_at {
                 state.expirations.remove(&(when, prev.id));
             }
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+            state.release_data(&prev.data);
         }
 
+        state.index_insert(&key, &materialized);
+        state.fan_out(&key, WatchEvent::Set(materialized));
+
         drop(state);
 
         if notify {
-            
+
             self.shared.background_task.notify_one();
         }
+
+        Ok(())
     }
 
     // Sets the value with the expiry of a key, only if the key does not exist
-    pub fn setnx(&self, key: Key, value: Bson, expire: Option<Duration>) -> bool {
+    pub fn setnx(&self, key: Key, value: Bson, expire: Option<Duration>) -> Result<bool, EiffelError> {
         let mut state = self.shared.state.write();
 
         if state.entries.contains_key(&key) {
-            return false
+            return Ok(false)
         }
 
         let id = state.next_id;
@@ -232,271 +688,417 @@ impl DataStore {
             when
         });
 
-        let prev = state.entries.insert(
-            key,
-            Entry {
-                id,
-                data: Arc::new(value),
-                expires_at,
-            },
-        );
+        let hlc = state.clock.tick();
+        let data = state.intern_value(value);
+        let entry = Entry { id, data, expires_at, hlc };
+        let materialized = state.entry_value(&entry);
+
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&materialized))?;
+        }
+
+        let prev = state.entries.insert(key.clone(), entry);
 
         if let Some(prev) = prev {
             if let Some(when) = prev.expires_at {
-                
+
                 state.expirations.remove(&(when, prev.id));
             }
+            state.release_data(&prev.data);
         }
 
+        state.index_insert(&key, &materialized);
+        state.fan_out(&key, WatchEvent::Set(materialized));
+
         drop(state);
 
         if notify {
             self.shared.background_task.notify_one();
         }
 
-        return true;
+        Ok(true)
     }
 
     // Sets multiple keys to multiple values
-    pub fn mset(&self, kvs: Vec<(Key, Bson)>) {
+    pub fn mset(&self, kvs: Vec<(Key, Bson)>) -> Result<(), EiffelError> {
         let mut state = self.shared.state.write();
 
         for (key, value) in kvs {
 
             let id = state.next_id;
             state.next_id += 1;
+            let hlc = state.clock.tick();
+            let data = state.intern_value(value);
+            let entry = Entry { id, data, expires_at: None, hlc };
+            let materialized = state.entry_value(&entry);
 
+            if let Some(backend) = &self.shared.backend {
+                backend.persist(&key, &entry.to_persisted(&materialized))?;
+            }
 
-            let prev = state.entries.insert(
-                key,
-                Entry {
-                    id,
-                    data: Arc::new(value),
-                    expires_at: None,
-                },
-            );
+            let prev = state.entries.insert(key.clone(), entry);
 
             if let Some(prev) = prev {
                 if let Some(when) = prev.expires_at {
                     state.expirations.remove(&(when, prev.id));
                 }
+                let prev_value = state.entry_value(&prev);
+                state.index_remove(&key, &prev_value);
+                state.release_data(&prev.data);
             }
 
+            state.index_insert(&key, &materialized);
+            state.fan_out(&key, WatchEvent::Set(materialized));
         }
 
+        Ok(())
     }
 
     // Sets multiple keys to multiple values, only if none of the keys exist
-    pub fn msetnx(&self, kvs: Vec<(Key, Bson)>) -> bool {
+    pub fn msetnx(&self, kvs: Vec<(Key, Bson)>) -> Result<bool, EiffelError> {
         let mut state = self.shared.state.write();
 
         for (key, _) in kvs.iter() {
             if let Some(_) = state.entries.get(key) {
-                return false;
+                return Ok(false);
             }
         }
 
         for (key, value) in kvs {
             let id = state.next_id;
             state.next_id += 1;
+            let hlc = state.clock.tick();
+            let data = state.intern_value(value);
+            let entry = Entry { id, data, expires_at: None, hlc };
+            let materialized = state.entry_value(&entry);
 
+            if let Some(backend) = &self.shared.backend {
+                backend.persist(&key, &entry.to_persisted(&materialized))?;
+            }
 
-            let prev = state.entries.insert(
-                key,
-                Entry {
-                    id,
-                    data: Arc::new(value),
-                    expires_at: None,
-                },
-            );
+            let prev = state.entries.insert(key.clone(), entry);
 
             if let Some(prev) = prev {
                 if let Some(when) = prev.expires_at {
                     state.expirations.remove(&(when, prev.id));
                 }
+                state.release_data(&prev.data);
             }
 
+            state.index_insert(&key, &materialized);
+            state.fan_out(&key, WatchEvent::Set(materialized));
         }
 
-        return true;
-
+        Ok(true)
     }
 
     // Gets the value by index.
     pub fn cursor(&self, cursor: usize) -> Option<Arc<Bson>> {
         let state = self.shared.state.read();
-        state.entries.get_index(cursor).map(|(_, entry)| entry.data.clone())
+        state.entries.get_index(cursor).map(|(_, entry)| state.entry_value(entry))
     }
 
 
     // Increments the integer value of a key by one ( This operation is limited to 64 bit signed integers )
     pub fn incr(&self, key: Key) -> Result<i64, EiffelError> {
         let mut state = self.shared.state.write();
-        
-        match state.entries.get_mut(&key) {
+
+        let (id, expires_at, result) = match state.entries.get(&key) {
             Some(entry) => {
-                match entry.data.as_i64() {
-                    None => {
-                        return Err(EiffelError::WrongType)
-                    }
-                    Some(counter) => {
-                        let c = counter + 1;
-                        entry.data = Arc::new(Bson::Int64(c));
-                        return Ok(c);
-                    }
-                }
+                let counter = entry.data.as_i64().ok_or(EiffelError::WrongType)?;
+                (entry.id, entry.expires_at, counter + 1)
             }
             None => {
-                
                 let id = state.next_id;
                 state.next_id += 1;
-
-                state.entries.insert(
-                    key,
-                    Entry {
-                        id,
-                        data: Arc::new(Bson::Int64(0)),
-                        expires_at: None,
-                    },
-                );
-            
-                return Ok(0);
+                (id, None, 0)
             }
         };
 
+        let hlc = state.clock.tick();
+        let entry = Entry { id, data: EntryData::Inline(Arc::new(Bson::Int64(result))), expires_at, hlc };
+
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&Bson::Int64(result)))?;
+        }
 
+        if let Some(prev) = state.entries.insert(key.clone(), entry) {
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+        }
+        state.index_insert(&key, &Bson::Int64(result));
+        state.fan_out(&key, WatchEvent::Incr(result));
+        Ok(result)
     }
 
 
-    // Increments the integer value of a key by one ( This operation is limited to 64 bit signed integers )
+    // Increments the integer value of a key by the given amount ( This operation is limited to 64 bit signed integers )
     pub fn incr_by(&self, key: Key, increment: i64) -> Result<i64, EiffelError> {
         let mut state = self.shared.state.write();
-        
-        match state.entries.get_mut(&key) {
+
+        let (id, expires_at, result) = match state.entries.get(&key) {
             Some(entry) => {
-                match entry.data.as_i64() {
-                    None => {
-                        return Err(EiffelError::WrongType)
-                    }
-                    Some(counter) => {
-                        let c = counter + increment;
-                        entry.data = Arc::new(Bson::Int64(c));
-                        return Ok(c);
-                    }
-                }
+                let counter = entry.data.as_i64().ok_or(EiffelError::WrongType)?;
+                (entry.id, entry.expires_at, counter + increment)
             }
             None => {
-                
                 let id = state.next_id;
                 state.next_id += 1;
-
-                state.entries.insert(
-                    key,
-                    Entry {
-                        id,
-                        data: Arc::new(Bson::Int64(increment)),
-                        expires_at: None,
-                    },
-                );
-            
-                return Ok(increment);
+                (id, None, increment)
             }
         };
 
+        let hlc = state.clock.tick();
+        let entry = Entry { id, data: EntryData::Inline(Arc::new(Bson::Int64(result))), expires_at, hlc };
 
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&Bson::Int64(result)))?;
+        }
+
+        if let Some(prev) = state.entries.insert(key.clone(), entry) {
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+        }
+        state.index_insert(&key, &Bson::Int64(result));
+        state.fan_out(&key, WatchEvent::Incr(result));
+        Ok(result)
     }
 
 
     // Decrements the integer value of a key by one ( This operation is limited to 64 bit signed integers )
     pub fn decr(&self, key: Key) -> Result<i64, EiffelError> {
         let mut state = self.shared.state.write();
-        
-        match state.entries.get_mut(&key) {
+
+        let (id, expires_at, result) = match state.entries.get(&key) {
             Some(entry) => {
-                match entry.data.as_i64() {
-                    None => {
-                        return Err(EiffelError::WrongType)
-                    }
-                    Some(counter) => {
-                        let c = counter - 1;
-                        entry.data = Arc::new(Bson::Int64(c));
-                        return Ok(c);
-                    }
-                }
+                let counter = entry.data.as_i64().ok_or(EiffelError::WrongType)?;
+                (entry.id, entry.expires_at, counter - 1)
             }
             None => {
-                
                 let id = state.next_id;
                 state.next_id += 1;
-
-                state.entries.insert(
-                    key,
-                    Entry {
-                        id,
-                        data: Arc::new(Bson::Int64(0)),
-                        expires_at: None,
-                    },
-                );
-            
-                return Ok(0);
+                (id, None, 0)
             }
         };
 
+        let hlc = state.clock.tick();
+        let entry = Entry { id, data: EntryData::Inline(Arc::new(Bson::Int64(result))), expires_at, hlc };
 
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&Bson::Int64(result)))?;
+        }
+
+        if let Some(prev) = state.entries.insert(key.clone(), entry) {
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+        }
+        state.index_insert(&key, &Bson::Int64(result));
+        state.fan_out(&key, WatchEvent::Incr(result));
+        Ok(result)
     }
 
 
     // Decrements the integer value of a key by the given number ( This operation is limited to 64 bit signed integers )
     pub fn decr_by(&self, key: Key, decrement: i64) -> Result<i64, EiffelError> {
         let mut state = self.shared.state.write();
-        
-        match state.entries.get_mut(&key) {
+
+        let (id, expires_at, result) = match state.entries.get(&key) {
             Some(entry) => {
-                match entry.data.as_i64() {
-                    None => {
-                        return Err(EiffelError::WrongType)
-                    }
-                    Some(counter) => {
-                        let c = counter - decrement;
-                        entry.data = Arc::new(Bson::Int64(c));
-                        return Ok(c);
-                    }
-                }
+                let counter = entry.data.as_i64().ok_or(EiffelError::WrongType)?;
+                (entry.id, entry.expires_at, counter - decrement)
             }
             None => {
-                
                 let id = state.next_id;
                 state.next_id += 1;
-
-                state.entries.insert(
-                    key,
-                    Entry {
-                        id,
-                        data: Arc::new(Bson::Int64(decrement)),
-                        expires_at: None,
-                    },
-                );
-            
-                return Ok(decrement);
+                (id, None, decrement)
             }
         };
 
+        let hlc = state.clock.tick();
+        let entry = Entry { id, data: EntryData::Inline(Arc::new(Bson::Int64(result))), expires_at, hlc };
 
+        if let Some(backend) = &self.shared.backend {
+            backend.persist(&key, &entry.to_persisted(&Bson::Int64(result)))?;
+        }
+
+        if let Some(prev) = state.entries.insert(key.clone(), entry) {
+            let prev_value = state.entry_value(&prev);
+            state.index_remove(&key, &prev_value);
+        }
+        state.index_insert(&key, &Bson::Int64(result));
+        state.fan_out(&key, WatchEvent::Incr(result));
+        Ok(result)
     }
 
 
 
-    pub fn del(&self, key: &Key) {
+    pub fn del(&self, key: &Key) -> Result<(), EiffelError> {
         let mut state = self.shared.state.write();
+
+        if !state.entries.contains_key(key) {
+            return Ok(());
+        }
+
+        if let Some(backend) = &self.shared.backend {
+            backend.remove(key)?;
+        }
+
         let entry = state.entries.remove(key);
         if let Some(entry) = entry {
             if let Some(when) = entry.expires_at {
                 state.expirations.remove(&(when, entry.id));
             }
+            let value = state.entry_value(&entry);
+            state.index_remove(key, &value);
+            state.release_data(&entry.data);
+        }
+
+        state.fan_out(key, WatchEvent::Deleted);
+
+        Ok(())
+    }
+
+    /// Subscribes to mutations on `key`. The returned stream yields a
+    /// `WatchEvent` for every `set`/`setex`/`del`/`incr`/expiry that
+    /// touches it, for as long as the stream (or the store) is alive.
+    pub fn watch(&self, key: Key) -> WatchStream {
+        let (tx, rx) = mpsc::channel(watch::CHANNEL_CAPACITY);
+        self.shared.state.write().watched_keys.entry(key).or_default().push(tx);
+        WatchStream { rx }
+    }
+
+    /// Subscribes to mutations on every key sharing `prefix`. See
+    /// [`watch::key_matches_prefix`] for what "sharing" means for
+    /// non-string keys.
+    pub fn subscribe_pattern(&self, prefix: Key) -> PatternWatchStream {
+        let (tx, rx) = mpsc::channel(watch::CHANNEL_CAPACITY);
+        self.shared.state.write().pattern_watchers.push((prefix, tx));
+        PatternWatchStream { rx }
+    }
+
+    /// Blocks until `key` appears or `timeout` elapses, whichever comes
+    /// first. Returns the value if the key showed up in time.
+    pub async fn get_await(&self, key: Key, timeout: Duration) -> Option<Arc<Bson>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let mut state = self.shared.state.write();
+            if let Some(entry) = state.entries.get(&key) {
+                let value = state.entry_value(entry);
+                prune_key_notify(&mut state, &key);
+                return Some(value);
+            }
+
+            let notify = state.key_notifies.entry(key.clone()).or_insert_with(|| Arc::new(Notify::new())).clone();
+
+            // `enable` this waiter on `notify` *before* releasing the write
+            // lock below: `fan_out` wakes only waiters that are already
+            // registered (via `notify_waiters`, which stores no permit for
+            // later), so a `set`/`del` landing in the gap between unlocking
+            // and the `select!` below would otherwise be missed entirely,
+            // and we'd block until `timeout` despite the key now existing.
+            let notified = notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            drop(state);
+
+            let now = Instant::now();
+            if now >= deadline {
+                prune_key_notify_after_wait(&self.shared, &key, &notify);
+                return None;
+            }
+
+            tokio::select! {
+                _ = notified => {}
+                _ = time::sleep_until(deadline) => {
+                    prune_key_notify_after_wait(&self.shared, &key, &notify);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Builds (or rebuilds) a secondary index over `field`, so an equality
+    /// predicate on it in a later `query` skips the full `entries` scan.
+    pub fn create_index(&self, field: FieldPath) {
+        let mut state = self.shared.state.write();
+
+        let mut index: BTreeMap<Vec<u8>, HashSet<Key>> = BTreeMap::new();
+        for (key, entry) in state.entries.iter() {
+            let value = state.entry_value(entry);
+            if let Some(indexed) = field.get(&value) {
+                index.entry(index_key(indexed)).or_default().insert(key.clone());
+            }
+        }
+
+        state.indexes.insert(field, index);
+    }
+
+    /// Scans (or, if `q`'s filter has an equality predicate on an indexed
+    /// field, looks up) entries matching `q.key_prefix` and `q.filter`,
+    /// skipping `q.cursor` matches and stopping once `q.limit` have been
+    /// collected.
+    pub fn query(&self, q: &Query) -> Vec<(Key, Arc<Bson>)> {
+        let state = self.shared.state.read();
+        let mut results = Vec::new();
+        let mut skipped = 0usize;
+
+        let mut visit = |key: &Key, entry: &Entry| -> bool {
+            if let Some(prefix) = &q.key_prefix {
+                if !watch::key_matches_prefix(key, prefix) {
+                    return true;
+                }
+            }
+            let value = state.entry_value(entry);
+            let keep = q.filter.as_ref().map_or(true, |f| f.matches(&value));
+            if !keep {
+                return true;
+            }
+            if skipped < q.cursor {
+                skipped += 1;
+                return true;
+            }
+            if q.limit.map_or(false, |limit| results.len() >= limit) {
+                return false;
+            }
+            results.push((key.clone(), value));
+            q.limit.map_or(true, |limit| results.len() < limit)
+        };
+
+        match indexed_candidates(&state, q.filter.as_ref()) {
+            Some(keys) => {
+                for key in &keys {
+                    if let Some(entry) = state.entries.get(key) {
+                        if !visit(key, entry) {
+                            break;
+                        }
+                    }
+                }
+            }
+            None => {
+                for (key, entry) in state.entries.iter() {
+                    if !visit(key, entry) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Starts a [`Batch`] of operations to apply atomically under a single
+    /// write lock — either every operation (and guard) succeeds, or none
+    /// of them are applied.
+    pub fn batch(&self) -> Batch {
+        Batch {
+            shared: self.shared.clone(),
+            ops: Vec::new(),
+            guards: Vec::new(),
         }
     }
 
- 
     fn shutdown_purge_task(&self) {
 
         let mut state = self.shared.state.write();
@@ -507,6 +1109,269 @@ impl DataStore {
     }
 }
 
+/// A single operation queued on a [`Batch`], not yet applied.
+enum Op {
+    Set(Key, Bson),
+    SetEx(Key, Bson, Option<Duration>),
+    Incr(Key, i64),
+    Del(Key),
+}
+
+impl Op {
+    fn key(&self) -> &Key {
+        match self {
+            Op::Set(key, _) => key,
+            Op::SetEx(key, _, _) => key,
+            Op::Incr(key, _) => key,
+            Op::Del(key) => key,
+        }
+    }
+}
+
+/// A precondition a [`Batch`] must meet at commit time, or the whole
+/// batch is rejected with none of its operations applied.
+enum Guard {
+    Exists(Key),
+    Version(Key, HlcTimestamp),
+}
+
+/// What a planned operation in a [`Batch`] would do to its key, computed
+/// (and durably persisted) before any operation is actually applied.
+#[derive(Clone)]
+struct PendingEntry {
+    id: u64,
+    value: Bson,
+    expires_at: Option<Instant>,
+    hlc: HlcTimestamp,
+}
+
+enum EventKind {
+    Set,
+    Incr,
+    Deleted,
+}
+
+/// A batch of `set`/`setex`/`incr`/`del` operations, plus optional
+/// `check_exists`/`check_version` guards, accumulated via the builder
+/// methods and applied all-or-nothing by [`Batch::commit`].
+pub struct Batch {
+    shared: Arc<Shared>,
+    ops: Vec<Op>,
+    guards: Vec<Guard>,
+}
+
+impl Batch {
+    pub fn set(mut self, key: Key, value: Bson) -> Self {
+        self.ops.push(Op::Set(key, value));
+        self
+    }
+
+    pub fn setex(mut self, key: Key, value: Bson, expire: Option<Duration>) -> Self {
+        self.ops.push(Op::SetEx(key, value, expire));
+        self
+    }
+
+    pub fn incr(mut self, key: Key, amount: i64) -> Self {
+        self.ops.push(Op::Incr(key, amount));
+        self
+    }
+
+    pub fn del(mut self, key: Key) -> Self {
+        self.ops.push(Op::Del(key));
+        self
+    }
+
+    /// Fails the commit unless `key` exists at commit time.
+    pub fn check_exists(mut self, key: Key) -> Self {
+        self.guards.push(Guard::Exists(key));
+        self
+    }
+
+    /// Fails the commit unless `key`'s current HLC timestamp is exactly
+    /// `expected` — a compare-and-swap guard for safe read-modify-write.
+    pub fn check_version(mut self, key: Key, expected: HlcTimestamp) -> Self {
+        self.guards.push(Guard::Version(key, expected));
+        self
+    }
+
+    /// Validates every guard, then computes every operation's effect —
+    /// including the fallible ones, like an `incr` on a non-counter value —
+    /// before durably persisting any of it. Only once the whole plan is
+    /// known good is it handed to the backend as a single `persist_batch`
+    /// call, and only then applied to `entries`: a guard failure or a
+    /// `WrongType` partway through the batch has zero backend side
+    /// effects, and no guard or caller ever observes a half-applied batch.
+    /// Once every operation has committed, `expirations`, `next_id` and
+    /// `fan_out` notifications are all fixed up in one pass.
+    pub fn commit(self) -> Result<(), EiffelError> {
+        let mut state = self.shared.state.write();
+
+        for guard in &self.guards {
+            let ok = match guard {
+                Guard::Exists(key) => state.entries.contains_key(key),
+                Guard::Version(key, expected) => {
+                    state.entries.get(key).map(|entry| entry.hlc) == Some(*expected)
+                }
+            };
+            if !ok {
+                return Err(EiffelError::GuardFailed);
+            }
+        }
+
+        let mut overlay: HashMap<Key, Option<PendingEntry>> = HashMap::new();
+        let mut plan: Vec<(Key, Option<PendingEntry>, EventKind)> = Vec::with_capacity(self.ops.len());
+        let mut next_id = state.next_id;
+
+        for op in &self.ops {
+            let key = op.key().clone();
+            let current = match overlay.get(&key) {
+                Some(pending) => pending.clone(),
+                None => state.entries.get(&key).map(|entry| PendingEntry {
+                    id: entry.id,
+                    value: (*state.entry_value(entry)).clone(),
+                    expires_at: entry.expires_at,
+                    hlc: entry.hlc,
+                }),
+            };
+
+            let (next, kind) = match op {
+                Op::Set(_, value) => {
+                    let id = current.as_ref().map_or_else(|| alloc_id(&mut next_id), |c| c.id);
+                    let hlc = state.clock.tick();
+                    (Some(PendingEntry { id, value: value.clone(), expires_at: None, hlc }), EventKind::Set)
+                }
+                Op::SetEx(_, value, expire) => {
+                    let id = current.as_ref().map_or_else(|| alloc_id(&mut next_id), |c| c.id);
+                    let expires_at = (*expire).map(|duration| Instant::now() + duration);
+                    let hlc = state.clock.tick();
+                    (Some(PendingEntry { id, value: value.clone(), expires_at, hlc }), EventKind::Set)
+                }
+                Op::Incr(_, amount) => {
+                    let base = match &current {
+                        Some(pending) => pending.value.as_i64().ok_or(EiffelError::WrongType)?,
+                        None => 0,
+                    };
+                    let id = current.as_ref().map_or_else(|| alloc_id(&mut next_id), |c| c.id);
+                    let expires_at = current.as_ref().and_then(|c| c.expires_at);
+                    let hlc = state.clock.tick();
+                    let result = base + *amount;
+                    (Some(PendingEntry { id, value: Bson::Int64(result), expires_at, hlc }), EventKind::Incr)
+                }
+                Op::Del(_) => (None, EventKind::Deleted),
+            };
+
+            overlay.insert(key.clone(), next.clone());
+            plan.push((key, next, kind));
+        }
+
+        if !plan.is_empty() {
+            if let Some(backend) = &self.shared.backend {
+                let batch: Vec<(Key, Option<PersistedEntry>)> = plan
+                    .iter()
+                    .map(|(key, next, _)| {
+                        let persisted = next.as_ref().map(|pending| {
+                            build_persisted(pending.id, &pending.value, pending.expires_at, pending.hlc)
+                        });
+                        (key.clone(), persisted)
+                    })
+                    .collect();
+                backend.persist_batch(&batch)?;
+            }
+        }
+        state.next_id = next_id;
+
+        let mut wake_purge = false;
+        let mut events = Vec::with_capacity(plan.len());
+
+        for (key, next, kind) in plan {
+            match next {
+                Some(pending) => {
+                    let data = state.intern_value(pending.value);
+                    let entry = Entry { id: pending.id, data, expires_at: pending.expires_at, hlc: pending.hlc };
+                    let materialized = state.entry_value(&entry);
+
+                    if let Some(when) = pending.expires_at {
+                        if state.next_expiration().map_or(true, |exp| exp > when) {
+                            wake_purge = true;
+                        }
+                        state.expirations.insert((when, pending.id), key.clone());
+                    }
+
+                    let prev = state.entries.insert(key.clone(), entry);
+                    if let Some(prev) = prev {
+                        if let Some(when) = prev.expires_at {
+                            state.expirations.remove(&(when, prev.id));
+                        }
+                        let prev_value = state.entry_value(&prev);
+                        state.index_remove(&key, &prev_value);
+                        state.release_data(&prev.data);
+                    }
+                    state.index_insert(&key, &materialized);
+
+                    let event = match kind {
+                        EventKind::Incr => WatchEvent::Incr(materialized.as_i64().unwrap_or_default()),
+                        _ => WatchEvent::Set(materialized),
+                    };
+                    events.push((key, event));
+                }
+                None => {
+                    if let Some(entry) = state.entries.remove(&key) {
+                        if let Some(when) = entry.expires_at {
+                            state.expirations.remove(&(when, entry.id));
+                        }
+                        let value = state.entry_value(&entry);
+                        state.index_remove(&key, &value);
+                        state.release_data(&entry.data);
+                    }
+                    events.push((key, WatchEvent::Deleted));
+                }
+            }
+        }
+
+        for (key, event) in events {
+            state.fan_out(&key, event);
+        }
+
+        drop(state);
+
+        if wake_purge {
+            self.shared.background_task.notify_one();
+        }
+
+        Ok(())
+    }
+}
+
+fn alloc_id(next_id: &mut u64) -> u64 {
+    let id = *next_id;
+    *next_id += 1;
+    id
+}
+
+/// Drops `key`'s `Notify` from `key_notifies` if nothing else is waiting
+/// on it, so a key that was once awaited doesn't leave an entry behind
+/// forever. Called with the write lock already held.
+fn prune_key_notify(state: &mut State, key: &Key) {
+    if let Some(existing) = state.key_notifies.get(key) {
+        if Arc::strong_count(existing) <= 1 {
+            state.key_notifies.remove(key);
+        }
+    }
+}
+
+/// Like [`prune_key_notify`], for a caller that's done waiting on `notify`
+/// and no longer holds the write lock. Only removes the map's entry if
+/// it's still the same `Notify` this caller was waiting on and no other
+/// `get_await` is also holding a clone of it.
+fn prune_key_notify_after_wait(shared: &Shared, key: &Key, notify: &Arc<Notify>) {
+    let mut state = shared.state.write();
+    if let Some(existing) = state.key_notifies.get(key) {
+        if Arc::ptr_eq(existing, notify) && Arc::strong_count(existing) <= 2 {
+            state.key_notifies.remove(key);
+        }
+    }
+}
+
 impl Shared {
     
     fn purge_expired_keys(&self) -> Option<Instant> {
@@ -524,8 +1389,17 @@ impl Shared {
                 return Some(when);
             }
 
-            state.entries.remove(key);
+            let key = key.clone();
+            if let Some(entry) = state.entries.remove(&key) {
+                if let Some(backend) = &self.backend {
+                    let _ = backend.remove(&key);
+                }
+                let value = state.entry_value(&entry);
+                state.index_remove(&key, &value);
+                state.release_data(&entry.data);
+            }
             state.expirations.remove(&(when, id));
+            state.fan_out(&key, WatchEvent::Expired);
         }
 
         None
@@ -544,20 +1418,64 @@ impl State {
             .next()
             .map(|expiration| expiration.0)
     }
+
+    // Delivers `event` to every direct watcher of `key` and every pattern
+    // watcher whose prefix matches it, pruning senders whose receiver was
+    // dropped. Sends are non-blocking: a subscriber that isn't keeping up
+    // just misses events instead of stalling the mutation that produced
+    // them.
+    fn fan_out(&mut self, key: &Key, event: WatchEvent) {
+        if let Some(senders) = self.watched_keys.get_mut(key) {
+            senders.retain(|sender| !matches!(
+                sender.try_send(event.clone()),
+                Err(mpsc::error::TrySendError::Closed(_))
+            ));
+        }
+
+        self.pattern_watchers.retain(|(prefix, sender)| {
+            if !watch::key_matches_prefix(key, prefix) {
+                return true;
+            }
+            !matches!(
+                sender.try_send((key.clone(), event.clone())),
+                Err(mpsc::error::TrySendError::Closed(_))
+            )
+        });
+
+        if let Some(notify) = self.key_notifies.get(key) {
+            notify.notify_waiters();
+        }
+    }
 }
 
 
 async fn purge_expired_tasks(shared: Arc<Shared>) {
     while !shared.is_shutdown() {
         if let Some(when) = shared.purge_expired_keys() {
-            
+
             tokio::select! {
                 _ = time::sleep_until(when) => {}
                 _ = shared.background_task.notified() => {}
             }
         } else {
-            
+
             shared.background_task.notified().await;
         }
     }
+}
+
+/// Drives `Backend::flush` on a fixed cadence for as long as the store is
+/// alive, the same way `purge_expired_tasks` drives expiry. A backend that
+/// is already durable per-call (e.g. `AofBackend`) just gets a no-op every
+/// tick.
+async fn flush_backend_task(shared: Arc<Shared>) {
+    while !shared.is_shutdown() {
+        time::sleep(BACKEND_FLUSH_INTERVAL).await;
+        if shared.is_shutdown() {
+            break;
+        }
+        if let Some(backend) = &shared.backend {
+            let _ = backend.flush();
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+//! Keyspace notifications: streams of [`WatchEvent`]s for callers that
+//! want to react to mutations instead of polling `get`.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bson::Bson;
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::key::Key;
+
+/// Bound of the per-subscriber channel. Subscribers are expected to keep
+/// up; a slow one does not block mutators (sends are non-blocking, see
+/// `State::fan_out` in `storage`), it just misses events once its buffer fills.
+pub(crate) const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    Set(Arc<Bson>),
+    Deleted,
+    Expired,
+    Incr(i64),
+}
+
+/// A live subscription to mutations on a single key, returned by
+/// `DataStore::watch`. Dropping it unsubscribes: the next mutation to the
+/// key finds the sender closed and prunes it.
+pub struct WatchStream {
+    pub(crate) rx: mpsc::Receiver<WatchEvent>,
+}
+
+impl Stream for WatchStream {
+    type Item = WatchEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// A live subscription to every key matching a prefix, returned by
+/// `DataStore::subscribe_pattern`.
+pub struct PatternWatchStream {
+    pub(crate) rx: mpsc::Receiver<(Key, WatchEvent)>,
+}
+
+impl Stream for PatternWatchStream {
+    type Item = (Key, WatchEvent);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Does `key` fall under `prefix`? Only `Key::String` supports genuine
+/// prefix matching since the other variants have no natural ordering a
+/// caller would describe as a "prefix" — they match only themselves.
+pub(crate) fn key_matches_prefix(key: &Key, prefix: &Key) -> bool {
+    match (key, prefix) {
+        (Key::String(k), Key::String(p)) => k.starts_with(p.as_str()),
+        _ => key == prefix,
+    }
+}